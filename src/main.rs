@@ -8,16 +8,17 @@ pub mod battleship {
 }
 
 pub mod services {
+    pub mod ai;
     pub mod battleship;
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let addr = "[::1]:50051".parse()?;
-    let service = BattleShipService::default();
+    let service = BattleShipService::new();
     let grpc_service = BattleshipServer::new(service);
 
-    Server::builder().add_service(service).serve(addr).await?;
+    Server::builder().add_service(grpc_service).serve(addr).await?;
 
     Ok(())
 }
@@ -0,0 +1,222 @@
+//! Built-in computer opponent.
+//!
+//! The engine targets with a probability-density heuristic. In *hunt* mode it
+//! scores every cell by how many legal placements of the still-floating enemy
+//! ships would cover it, then fires at the densest un-guessed cell. Once a shot
+//! hits without sinking, it switches to *target* mode and only considers
+//! placements that also cover an unresolved hit, concentrating fire around the
+//! wounded ship until it sinks.
+
+/// Outcome the engine has recorded for a cell it has already fired at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shot {
+    Hit,
+    Miss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+    x: u32,
+    y: u32,
+}
+
+#[derive(Debug)]
+pub struct AiPlayer {
+    board_size: usize,
+    /// The engine's view of the enemy board; `None` means un-guessed.
+    shots: Vec<Vec<Option<Shot>>>,
+    /// Lengths of enemy ships still believed to be afloat.
+    remaining_ships: Vec<usize>,
+    /// Hits not yet attributed to a sunk ship, driving target mode.
+    unresolved_hits: Vec<Cell>,
+}
+
+impl AiPlayer {
+    /// Build an engine for a `board_size`×`board_size` board against a fleet
+    /// described by its ship lengths.
+    pub fn new(board_size: usize, ship_lengths: Vec<usize>) -> Self {
+        Self {
+            board_size,
+            shots: vec![vec![None; board_size]; board_size],
+            remaining_ships: ship_lengths,
+            unresolved_hits: Vec::new(),
+        }
+    }
+
+    /// The next cell to fire at, or `None` when no un-guessed cell is covered by
+    /// a legal placement of the remaining ships (e.g. once every ship has sunk).
+    pub fn next_guess(&self) -> Option<(u32, u32)> {
+        let target_mode = !self.unresolved_hits.is_empty();
+        // A target-mode grid can come back all-zero when the unresolved hits no
+        // longer line up with any legal placement (e.g. after a mis-attributed
+        // sink orphans them); fall back to hunt mode rather than stalling.
+        self.best_cell(&self.density(target_mode))
+            .or_else(|| target_mode.then(|| self.best_cell(&self.density(false))).flatten())
+    }
+
+    /// Pick the empty, un-guessed cell with the highest density, breaking ties
+    /// toward the lowest `(y, x)` so the choice is deterministic.
+    fn best_cell(&self, grid: &[Vec<u32>]) -> Option<(u32, u32)> {
+        let mut best: Option<((usize, usize), u32)> = None;
+        for y in 0..self.board_size {
+            for x in 0..self.board_size {
+                if self.shots[y][x].is_some() || grid[y][x] == 0 {
+                    continue;
+                }
+                // Strict `>` keeps the first (lowest y, then x) cell on ties.
+                match best {
+                    Some((_, b)) if grid[y][x] <= b => {}
+                    _ => best = Some(((x, y), grid[y][x])),
+                }
+            }
+        }
+        best.map(|((x, y), _)| (x as u32, y as u32))
+    }
+
+    /// Feed back the result of a shot so the next guess can adapt.
+    pub fn record(&mut self, x: u32, y: u32, hit: bool, sunk: bool) {
+        self.shots[y as usize][x as usize] = Some(if hit { Shot::Hit } else { Shot::Miss });
+        if hit {
+            self.unresolved_hits.push(Cell { x, y });
+            if sunk {
+                self.resolve_sink(x as usize, y as usize);
+            }
+        }
+    }
+
+    /// Accumulate, over every still-floating ship and every legal placement, a
+    /// per-cell count of the placements that would cover it. In `target_mode`
+    /// only placements covering at least one unresolved hit are counted.
+    fn density(&self, target_mode: bool) -> Vec<Vec<u32>> {
+        let n = self.board_size;
+        let mut grid = vec![vec![0u32; n]; n];
+        for &len in &self.remaining_ships {
+            for (dx, dy) in [(1usize, 0usize), (0, 1)] {
+                for y in 0..n {
+                    for x in 0..n {
+                        if x + dx * (len - 1) >= n || y + dy * (len - 1) >= n {
+                            continue;
+                        }
+                        let cells: Vec<(usize, usize)> =
+                            (0..len).map(|i| (x + dx * i, y + dy * i)).collect();
+                        if cells
+                            .iter()
+                            .any(|&(cx, cy)| self.shots[cy][cx] == Some(Shot::Miss))
+                        {
+                            continue;
+                        }
+                        if target_mode
+                            && !cells.iter().any(|&(cx, cy)| self.is_unresolved_hit(cx, cy))
+                        {
+                            continue;
+                        }
+                        for (cx, cy) in cells {
+                            grid[cy][cx] += 1;
+                        }
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    fn is_unresolved_hit(&self, x: usize, y: usize) -> bool {
+        self.unresolved_hits
+            .iter()
+            .any(|c| c.x as usize == x && c.y as usize == y)
+    }
+
+    /// A ship covering `(x, y)` just sank: drop one ship of the matching length
+    /// from the inventory, clear the connected hits, and (if no hits remain)
+    /// fall back to hunt mode implicitly.
+    fn resolve_sink(&mut self, x: usize, y: usize) {
+        let mut cluster = vec![(x, y)];
+        let mut i = 0;
+        while i < cluster.len() {
+            let (cx, cy) = cluster[i];
+            for (nx, ny) in self.orthogonal(cx, cy) {
+                if self.is_unresolved_hit(nx, ny) && !cluster.contains(&(nx, ny)) {
+                    cluster.push((nx, ny));
+                }
+            }
+            i += 1;
+        }
+
+        let len = cluster.len();
+        let pos = self
+            .remaining_ships
+            .iter()
+            .position(|&l| l == len)
+            .or_else(|| {
+                // The cluster may span more than one ship; remove the closest
+                // length so the inventory still shrinks.
+                self.remaining_ships
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, &l)| l.abs_diff(len))
+                    .map(|(idx, _)| idx)
+            });
+        if let Some(pos) = pos {
+            self.remaining_ships.remove(pos);
+        }
+        self.unresolved_hits
+            .retain(|c| !cluster.contains(&(c.x as usize, c.y as usize)));
+    }
+
+    fn orthogonal(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let mut neighbors = Vec::with_capacity(4);
+        if x > 0 {
+            neighbors.push((x - 1, y));
+        }
+        if x + 1 < self.board_size {
+            neighbors.push((x + 1, y));
+        }
+        if y > 0 {
+            neighbors.push((x, y - 1));
+        }
+        if y + 1 < self.board_size {
+            neighbors.push((x, y + 1));
+        }
+        neighbors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hunt_targets_densest_cell() {
+        // On an empty 5×5 board the centre has the most length-3 placements
+        // covering it, and ties break toward the lowest (y, x).
+        let ai = AiPlayer::new(5, vec![3]);
+        assert_eq!(ai.next_guess(), Some((2, 2)));
+    }
+
+    #[test]
+    fn hunt_avoids_missed_cells() {
+        let mut ai = AiPlayer::new(5, vec![3]);
+        ai.record(2, 2, false, false);
+        assert_ne!(ai.next_guess(), Some((2, 2)));
+    }
+
+    #[test]
+    fn target_mode_focuses_on_a_neighbor() {
+        let mut ai = AiPlayer::new(5, vec![3]);
+        ai.record(2, 2, true, false);
+        let guess = ai.next_guess().expect("a follow-up shot");
+        let neighbors = [(1, 2), (3, 2), (2, 1), (2, 3)];
+        assert!(neighbors.contains(&guess), "{guess:?} is not adjacent to the hit");
+    }
+
+    #[test]
+    fn sinking_a_ship_clears_hits_and_resumes_hunt() {
+        let mut ai = AiPlayer::new(5, vec![2]);
+        ai.record(0, 0, true, false);
+        ai.record(1, 0, true, true);
+        assert!(ai.remaining_ships.is_empty());
+        assert!(ai.unresolved_hits.is_empty());
+        // With no ships left to place, the density grid is empty.
+        assert_eq!(ai.next_guess(), None);
+    }
+}
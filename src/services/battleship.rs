@@ -1,35 +1,50 @@
 use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
 
-use tokio::sync::mpsc::{channel, Sender};
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, mpsc, Mutex};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
 use tonic::{Request, Response, Status, Streaming};
 
-use crate::battleship::{battleship_server::Battleship, Player};
+use crate::services::ai::AiPlayer;
 
-use crate::battleship::{Guess, ShipPlacement};
+use crate::battleship::battleship_server::Battleship;
+use crate::battleship::game_event::Payload;
+use crate::battleship::{
+    BoardCell, BoardSnapshot, CellResult, CreateGameRequest, Empty, GameEvent, GameId, GameOver,
+    GameStateView, Guess, GuessResult, PlacementResult, Player, PlayerInfo, PlayerJoined,
+    PlayerLeft, PlayerTurn, ShipPlacement, ShipSunk, ShotFired,
+};
 
-const BOARD_SIZE: usize = 10;
-const CARRIER_LENGTH: usize = 5;
-const BATTLESHIP_LENGTH: usize = 4;
-const CRUISER_LENGTH: usize = 3;
-const SUBMARINE_LENGTH: usize = 3;
-const DESTROYER_LENGTH: usize = 2;
-
-#[derive(Debug, Clone)]
-struct Cell {
-    x: u32,
-    y: u32,
+/// High-level phase of a single match. The service only accepts the RPCs that
+/// make sense for the current phase and drives the transitions itself, so the
+/// handlers never have to second-guess whether an action is legal yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GameStatus {
+    /// Fewer than two players have registered.
+    Created,
+    /// Both players are registered but have not finished placing their fleets.
+    WaitingForPlacement,
+    /// Both fleets are placed; players are exchanging fire.
+    InProgress,
+    /// One fleet has been sunk; the game is over.
+    Finished,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum CellState {
-    Empty,
-    Occupied,
-    Hit,
-    Miss,
+impl GameStatus {
+    /// The protobuf enum discriminant, for `GameStateView`.
+    fn as_proto(self) -> i32 {
+        match self {
+            GameStatus::Created => 0,
+            GameStatus::WaitingForPlacement => 1,
+            GameStatus::InProgress => 2,
+            GameStatus::Finished => 3,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum ShipType {
     Carrier,
     Battleship,
@@ -38,41 +53,167 @@ enum ShipType {
     Destroyer,
 }
 
-#[derive(Debug)]
-enum Ship {
-    Carrier(Vec<Cell>),
-    Battleship(Vec<Cell>),
-    Cruiser(Vec<Cell>),
-    Submarine(Vec<Cell>),
-    Destroyer(Vec<Cell>),
+impl ShipType {
+    /// Number of cells the ship occupies on the board.
+    fn length(self) -> usize {
+        match self {
+            ShipType::Carrier => 5,
+            ShipType::Battleship => 4,
+            ShipType::Cruiser => 3,
+            ShipType::Submarine => 3,
+            ShipType::Destroyer => 2,
+        }
+    }
+
+    /// Parse the wire representation used by `ShipPlacement::ship_type`.
+    fn from_wire(name: &str) -> Option<Self> {
+        match name {
+            "carrier" => Some(ShipType::Carrier),
+            "battleship" => Some(ShipType::Battleship),
+            "cruiser" => Some(ShipType::Cruiser),
+            "submarine" => Some(ShipType::Submarine),
+            "destroyer" => Some(ShipType::Destroyer),
+            _ => None,
+        }
+    }
+
+    /// The wire representation used by `ShipPlacement::ship_type`.
+    fn wire_name(self) -> &'static str {
+        match self {
+            ShipType::Carrier => "carrier",
+            ShipType::Battleship => "battleship",
+            ShipType::Cruiser => "cruiser",
+            ShipType::Submarine => "submarine",
+            ShipType::Destroyer => "destroyer",
+        }
+    }
 }
 
-#[derive(Debug)]
+/// Configurable match parameters, replacing the old hardcoded board/ship
+/// constants so a single server build can host differently-sized games.
+#[derive(Debug, Clone)]
+struct GameRules {
+    board_size: usize,
+    /// Which ship types must be placed and how many of each.
+    fleet: Vec<(ShipType, u32)>,
+    allow_diagonal: bool,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            board_size: 10,
+            fleet: vec![
+                (ShipType::Carrier, 1),
+                (ShipType::Battleship, 1),
+                (ShipType::Cruiser, 1),
+                (ShipType::Submarine, 1),
+                (ShipType::Destroyer, 1),
+            ],
+            allow_diagonal: false,
+        }
+    }
+}
+
+impl GameRules {
+    /// Required count for a ship type, or 0 if it is not part of the fleet.
+    fn required(&self, ship: ShipType) -> u32 {
+        self.fleet
+            .iter()
+            .find(|(t, _)| *t == ship)
+            .map(|(_, n)| *n)
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CellState {
+    Empty,
+    Occupied,
+    Hit,
+    Miss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+    x: u32,
+    y: u32,
+}
+
+#[derive(Debug, Clone)]
 struct PlayerState {
     id: String,
-    ships: Vec<Ship>,
+    name: String,
+    /// The player's own waters: ship type occupying each cell, if any.
+    ship_positions: Vec<Vec<Option<ShipType>>>,
+    /// Shot state of the player's own waters (what the opponent has revealed).
+    board: Vec<Vec<CellState>>,
+    /// Cells occupied by each placed ship, grouped by type.
+    ship_cells: HashMap<ShipType, Vec<Vec<Cell>>>,
+    /// Cells this player has already fired at.
     guesses: Vec<(u32, u32)>,
+    /// Special-weapon charges currently available to spend.
+    charges: u32,
+    /// Normal hits accumulated toward the next charge.
+    hits_toward_charge: u32,
 }
 
+/// Number of normal hits that earns one special-weapon charge.
+const HITS_PER_CHARGE: u32 = 3;
+
 impl PlayerState {
-    fn new(id: String) -> Self {
+    fn new(id: String, name: String, board_size: usize) -> Self {
         Self {
             id,
-            ships: Vec::new(),
+            name,
+            ship_positions: vec![vec![None; board_size]; board_size],
+            board: vec![vec![CellState::Empty; board_size]; board_size],
+            ship_cells: HashMap::new(),
             guesses: Vec::new(),
+            charges: 0,
+            hits_toward_charge: 0,
         }
     }
+
+    /// Whether every ship type required by the rules has been placed the
+    /// required number of times.
+    fn fleet_ready(&self, rules: &GameRules) -> bool {
+        rules
+            .fleet
+            .iter()
+            .all(|(ship, count)| self.ship_cells.get(ship).map_or(0, |v| v.len() as u32) == *count)
+    }
 }
 
 #[derive(Debug)]
 struct GameStateInner {
-    board: [[CellState; BOARD_SIZE]; BOARD_SIZE],
-    ships_remaining: HashMap<ShipType, u32>,
-    ship_positions: [[Option<ShipType>; BOARD_SIZE]; BOARD_SIZE],
-    ship_cells: HashMap<ShipType, Vec<Cell>>,
-    remaining_ships: Vec<ShipType>,
-    turn: Option<Player>,
-    events_tx: Sender<Event>,
+    rules: GameRules,
+    status: GameStatus,
+    players: Vec<PlayerState>,
+    /// Id of the player whose turn it is, once the game is in progress.
+    turn: Option<String>,
+    /// Computer opponent, if one was registered as the second player.
+    ai: Option<AiOpponent>,
+    events_tx: broadcast::Sender<Event>,
+}
+
+/// A registered computer player: its player id plus the targeting engine that
+/// chooses its shots.
+#[derive(Debug)]
+struct AiOpponent {
+    id: String,
+    engine: AiPlayer,
+}
+
+impl GameStateInner {
+    fn player_mut(&mut self, id: &str) -> Option<&mut PlayerState> {
+        self.players.iter_mut().find(|p| p.id == id)
+    }
+
+    /// Index of the player that is *not* `id`, for two-player lookups.
+    fn opponent_index(&self, id: &str) -> Option<usize> {
+        self.players.iter().position(|p| p.id != id)
+    }
 }
 
 #[derive(Debug)]
@@ -80,161 +221,905 @@ struct GameState {
     inner: Mutex<GameStateInner>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum Event {
     PlayerJoined(Player),
     PlayerLeft(Player),
-    PlayerTurn(Player),
-    MakeGuess(Guess),
+    PlayerTurn(String),
+    ShotFired {
+        player_id: String,
+        x: u32,
+        y: u32,
+        hit: bool,
+    },
+    ShipSunk {
+        owner_id: String,
+        ship_type: String,
+    },
+    GameOver {
+        winner_id: String,
+    },
 }
 
+impl Event {
+    /// Map the internal event onto the wire `GameEvent` oneof.
+    fn to_proto(&self) -> GameEvent {
+        let payload = match self {
+            Event::PlayerJoined(p) => Payload::PlayerJoined(PlayerJoined { name: p.name.clone() }),
+            Event::PlayerLeft(p) => Payload::PlayerLeft(PlayerLeft { name: p.name.clone() }),
+            Event::PlayerTurn(id) => Payload::PlayerTurn(PlayerTurn {
+                player_id: id.clone(),
+            }),
+            Event::ShotFired {
+                player_id,
+                x,
+                y,
+                hit,
+            } => Payload::ShotFired(ShotFired {
+                player_id: player_id.clone(),
+                x: *x,
+                y: *y,
+                hit: *hit,
+            }),
+            Event::ShipSunk {
+                owner_id,
+                ship_type,
+            } => Payload::ShipSunk(ShipSunk {
+                owner_id: owner_id.clone(),
+                ship_type: ship_type.clone(),
+            }),
+            Event::GameOver { winner_id } => Payload::GameOver(GameOver {
+                winner_id: winner_id.clone(),
+            }),
+        };
+        GameEvent {
+            payload: Some(payload),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct BattleShipService {
-    game_state: GameState,
+    /// Default rules used for games created without an explicit config.
+    rules: GameRules,
+    /// Every live match, keyed by its game id.
+    games: Mutex<HashMap<String, Arc<GameState>>>,
+}
+
+impl Default for BattleShipService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BattleShipService {
-    async fn new() -> Self {
-        let (events_tx, events_rx) = channel(100);
-        let game_state = GameState {
+    pub fn new() -> Self {
+        Self::with_rules(GameRules::default())
+    }
+
+    pub fn with_rules(rules: GameRules) -> Self {
+        Self {
+            rules,
+            games: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Allocate a fresh `GameState` seeded from `rules` and spawn its event
+    /// printer. Returns the shared handle ready to be inserted into the lobby.
+    fn spawn_game(rules: GameRules) -> Arc<GameState> {
+        let (events_tx, mut events_rx) = broadcast::channel(100);
+        // Keep one subscriber alive so `send` never fails for lack of
+        // receivers, and log every event for observability.
+        tokio::spawn(async move {
+            loop {
+                match events_rx.recv().await {
+                    Ok(event) => println!("Event: {:?}", event),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        Arc::new(GameState {
             inner: Mutex::new(GameStateInner {
-                board: [[CellState::Empty; BOARD_SIZE]; BOARD_SIZE],
-                ships_remaining: HashMap::new(),
-                ship_positions: [[None; BOARD_SIZE]; BOARD_SIZE],
-                ship_cells: HashMap::new(),
-                remaining_ships: Vec::new(),
+                rules,
+                status: GameStatus::Created,
+                players: Vec::new(),
                 turn: None,
+                ai: None,
                 events_tx,
             }),
-        };
-        tokio::spawn(async move {
-            while let Some(event) = events_rx.recv().await {
-                match event {
-                    Event::PlayerJoined(player) => {
-                        println!("Player joined: {:?}", player);
-                    }
-                    Event::PlayerLeft(player) => {
-                        println!("Player left: {:?}", player);
-                    }
-                    Event::PlayerTurn(player) => {
-                        println!("Player turn: {:?}", player);
-                    }
-                    Event::MakeGuess(guess) => {
-                        println!("Guess: {:?}", guess);
-                    }
-                }
-            }
+        })
+    }
+
+    /// Look up a live game by id, or `Status::not_found` if it is unknown.
+    async fn game(&self, game_id: &str) -> Result<Arc<GameState>, Status> {
+        self.games
+            .lock()
+            .await
+            .get(game_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found("unknown game id"))
+    }
+
+    /// Drop a game from the lobby once nobody is left in it or it is over.
+    async fn cleanup(&self, game_id: &str, game: &GameState) {
+        let inner = game.inner.lock().await;
+        if inner.players.is_empty() || inner.status == GameStatus::Finished {
+            drop(inner);
+            self.games.lock().await.remove(game_id);
+        }
+    }
+
+    /// Register a computer opponent in `game_id` as the second player,
+    /// auto-placing its fleet, so a single human can play solo. Drive it
+    /// through the normal `make_guess` path whenever the turn comes round.
+    pub async fn register_ai(&self, game_id: &str) -> Result<(), Status> {
+        let game = self.game(game_id).await?;
+        let mut game_state = game.inner.lock().await;
+        if game_state.players.len() >= 2 {
+            return Err(Status::failed_precondition("game already has two players"));
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let board_size = game_state.rules.board_size;
+        let rules = game_state.rules.clone();
+        let mut player = PlayerState::new(id.clone(), "Computer".to_string(), board_size);
+        auto_place_fleet(&mut player, &rules)
+            .map_err(|_| Status::internal("could not place computer fleet"))?;
+        game_state.players.push(player);
+        game_state.ai = Some(AiOpponent {
+            id,
+            engine: AiPlayer::new(board_size, fleet_lengths(&rules)),
         });
-        Self { game_state }
+
+        if game_state.players.len() == 2 && game_state.status == GameStatus::Created {
+            game_state.status = GameStatus::WaitingForPlacement;
+        }
+        if game_state.players.len() == 2 && game_state.players.iter().all(|p| p.fleet_ready(&rules)) {
+            game_state.status = GameStatus::InProgress;
+            game_state.turn = game_state.players.first().map(|p| p.id.clone());
+            // Play the opening turn if it fell to the computer.
+            drive_ai(&mut game_state);
+        }
+        Ok(())
+    }
+}
+
+/// Expand a placement into the cells it occupies. Returns `invalid_argument`
+/// if the orientation is unknown, if a diagonal is requested while the rules
+/// forbid it, or if any cell would fall outside the board.
+fn expand_placement(
+    placement: &ShipPlacement,
+    ship: ShipType,
+    board_size: usize,
+    allow_diagonal: bool,
+) -> Result<Vec<Cell>, Status> {
+    // Orientation 0 advances x (horizontal), 1 advances y (vertical), 2
+    // advances both (down-right diagonal) when the rules permit it.
+    let (dx, dy) = match placement.orientation {
+        0 => (1, 0),
+        1 => (0, 1),
+        2 if allow_diagonal => (1, 1),
+        2 => {
+            return Err(Status::invalid_argument(
+                "diagonal placement is not allowed in this game",
+            ))
+        }
+        _ => return Err(Status::invalid_argument("unknown orientation")),
+    };
+    let (mut x, mut y) = (placement.x, placement.y);
+    let mut cells = Vec::with_capacity(ship.length());
+    for _ in 0..ship.length() {
+        if x as usize >= board_size || y as usize >= board_size {
+            return Err(Status::invalid_argument("ship does not fit on the board"));
+        }
+        cells.push(Cell { x, y });
+        x += dx;
+        y += dy;
+    }
+    Ok(cells)
+}
+
+/// Validate and apply a single ship placement for `player` under `rules`,
+/// stamping its cells into `ship_positions`/`board`/`ship_cells`. Returns a
+/// specific `invalid_argument`/`already_exists` status when the ship type is
+/// unknown or unwanted, the ship falls off the board, the type has already
+/// been placed its required number of times, or a cell overlaps another ship.
+fn place_one(
+    player: &mut PlayerState,
+    rules: &GameRules,
+    placement: &ShipPlacement,
+) -> Result<(), Status> {
+    let ship = ShipType::from_wire(&placement.ship_type)
+        .ok_or_else(|| Status::invalid_argument("unknown ship type"))?;
+    let required = rules.required(ship);
+    if required == 0 {
+        return Err(Status::invalid_argument(format!(
+            "{} is not part of this game's fleet",
+            placement.ship_type
+        )));
+    }
+    let cells = expand_placement(placement, ship, rules.board_size, rules.allow_diagonal)?;
+
+    let already_placed = player.ship_cells.get(&ship).map_or(0, |v| v.len() as u32);
+    if already_placed >= required {
+        return Err(Status::already_exists(format!(
+            "all {} {}(s) have already been placed",
+            required, placement.ship_type
+        )));
+    }
+    if let Some(cell) = cells
+        .iter()
+        .find(|c| player.ship_positions[c.y as usize][c.x as usize].is_some())
+    {
+        return Err(Status::already_exists(format!(
+            "cell ({}, {}) is already occupied by another ship",
+            cell.x, cell.y
+        )));
+    }
+
+    for cell in &cells {
+        player.ship_positions[cell.y as usize][cell.x as usize] = Some(ship);
+        player.board[cell.y as usize][cell.x as usize] = CellState::Occupied;
     }
+    player.ship_cells.entry(ship).or_default().push(cells);
+    Ok(())
 }
 
+/// Server-streaming type for `subscribe_events`.
+type EventStream = Pin<Box<dyn Stream<Item = Result<GameEvent, Status>> + Send + 'static>>;
+
 #[tonic::async_trait]
 impl Battleship for BattleShipService {
+    type SubscribeEventsStream = EventStream;
+
+    async fn create_game(
+        &self,
+        request: Request<CreateGameRequest>,
+    ) -> Result<Response<GameId>, Status> {
+        let config = request.into_inner();
+        let mut rules = self.rules.clone();
+        if config.board_size != 0 {
+            rules.board_size = config.board_size as usize;
+        }
+        rules.allow_diagonal = config.allow_diagonal;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let game = Self::spawn_game(rules);
+        self.games.lock().await.insert(id.clone(), game);
+        Ok(Response::new(GameId { id }))
+    }
+
+    async fn join_game(&self, request: Request<Player>) -> Result<Response<PlayerInfo>, Status> {
+        let player = request.into_inner();
+        let game = self.game(&player.game_id).await?;
+        let info = register_into(&game, &player).await?;
+        Ok(Response::new(info))
+    }
+
+    async fn add_computer(&self, request: Request<GameId>) -> Result<Response<Empty>, Status> {
+        self.register_ai(&request.into_inner().id).await?;
+        Ok(Response::new(Empty {}))
+    }
+
     async fn register_player(
         &self,
         request: Request<Player>,
     ) -> Result<Response<PlayerInfo>, Status> {
         let player = request.into_inner();
-        let id = uuid::Uuid::new_v4().to_string();
-        let player_info = PlayerInfo { id: id.clone() };
-        let mut game_state = self.game_state.inner.lock().await;
-        game_state
-            .events_tx
-            .send(Event::PlayerJoined(player.clone()))
-            .await
-            .unwrap();
-        Ok(Response::new(player_info))
+        let game = self.game(&player.game_id).await?;
+        let info = register_into(&game, &player).await?;
+        Ok(Response::new(info))
     }
 
     async fn remove_player(&self, request: Request<Player>) -> Result<Response<Empty>, Status> {
         let player = request.into_inner();
-        let mut game_state = self.game_state.inner.lock().await;
-        game_state
-            .events_tx
-            .send(Event::PlayerLeft(player.clone()))
-            .await
-            .unwrap();
+        let game = self.game(&player.game_id).await?;
+        {
+            let mut game_state = game.inner.lock().await;
+            game_state.players.retain(|p| p.id != player.id);
+            let _ = game_state.events_tx.send(Event::PlayerLeft(player.clone()));
+        }
+        self.cleanup(&player.game_id, &game).await;
         Ok(Response::new(Empty {}))
     }
 
     async fn place_ship(
         &self,
-        request: tonic::Request<Streaming<ShipPlacement>>,
-    ) -> Result<Response<Ship>, Status> {
+        request: Request<Streaming<ShipPlacement>>,
+    ) -> Result<Response<PlacementResult>, Status> {
         let mut stream = request.into_inner();
-        let first_ship = stream
-            .next()
-            .await
-            .ok_or_else(|| Status::invalid_argument("No ship placement in stream"))??;
-
-        let mut game_state = self.game_state.inner.lock().await;
-        let player_id = first_ship.player_id.clone();
-        let player_state = game_state
-            .ship_positions
-            .get(&player_id)
-            .unwrap_or_else(|| {
-                game_state.ships_remaining.insert(ShipType::Carrier, 1);
-                game_state.ships_remaining.insert(ShipType::Battleship, 1);
-                game_state.ships_remaining.insert(ShipType::Cruiser, 1);
-                game_state.ships_remaining.insert(ShipType::Submarine, 1);
-                game_state.ships_remaining.insert(ShipType::Destroyer, 1);
-                game_state
-                    .remaining_ships
-                    .push(ShipType::Carrier)
-                    .push(ShipType::Battleship)
-                    .push(ShipType::Cruiser)
-                    .push(ShipType::Submarine)
-                    .push(ShipType::Destroyer);
-                game_state
-                    .ship_positions
-                    .insert(player_id.clone(), [[None; BOARD_SIZE]; BOARD_SIZE]);
-                game_state
-                    .ship_cells
-                    .insert(ShipType::Carrier, Vec::new())
-                    .insert(ShipType::Battleship, Vec::new())
-                    .insert(ShipType::Cruiser, Vec::new())
-                    .insert(ShipType::Submarine, Vec::new())
-                    .insert(ShipType::Destroyer, Vec::new());
-                PlayerState::new(player_id.clone())
-            })
-            .clone();
-
-        for placement in stream {
-            let placement = placement?;
-
-            let ship_type = match placement.ship_type.as_str() {
-                "carrier" => ShipType::Carrier,
-                "battleship" => ShipType::Battleship,
-                "cruiser" => ShipType::Cruiser,
-                "submarine" => ShipType::Submarine,
-                "destroyer" => ShipType::Destroyer,
-                _ => return Err(Status::invalid_argument("Invalid ship type")),
-            };
+        let first = stream
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("no ship placement in stream"))?;
+        let game = self.game(&first.game_id).await?;
+        let mut game_state = game.inner.lock().await;
+        if game_state.status != GameStatus::WaitingForPlacement {
+            return Err(Status::failed_precondition(
+                "ships can only be placed while waiting for placement",
+            ));
+        }
+
+        let rules = game_state.rules.clone();
+        // Stage the whole batch on per-player clones so a rejected stream
+        // leaves every board untouched and the client can re-stream cleanly;
+        // only commit once every placement in the stream validates.
+        let mut staged: HashMap<String, PlayerState> = HashMap::new();
+        let mut next = Some(first);
+        while let Some(placement) = next {
+            if !staged.contains_key(&placement.player_id) {
+                let original = game_state
+                    .players
+                    .iter()
+                    .find(|p| p.id == placement.player_id)
+                    .ok_or_else(|| Status::not_found("unknown player"))?;
+                staged.insert(placement.player_id.clone(), original.clone());
+            }
+            let player = staged.get_mut(&placement.player_id).expect("just staged");
+            place_one(player, &rules, &placement)?;
+            next = stream.message().await?;
+        }
+        for (id, player) in staged {
+            if let Some(slot) = game_state.player_mut(&id) {
+                *slot = player;
+            }
+        }
 
-            if !game_state.remaining_ships.contains(&ship_type) {
-                return Err(Status::invalid_argument(
-                    "All ships of this type are already placed",
+        let all_ready =
+            game_state.players.len() == 2 && game_state.players.iter().all(|p| p.fleet_ready(&rules));
+        if all_ready {
+            game_state.status = GameStatus::InProgress;
+            game_state.turn = game_state.players.first().map(|p| p.id.clone());
+            // The opening turn may land on the computer; play it now so control
+            // returns to the caller instead of deadlocking on the AI's seat.
+            drive_ai(&mut game_state);
+        }
+
+        Ok(Response::new(PlacementResult {
+            complete: all_ready,
+        }))
+    }
+
+    async fn make_guess(&self, request: Request<Guess>) -> Result<Response<GuessResult>, Status> {
+        let guess = request.into_inner();
+        let game = self.game(&guess.game_id).await?;
+        let (outcomes, finished) = {
+            let mut game_state = game.inner.lock().await;
+            if game_state.status != GameStatus::InProgress {
+                return Err(Status::failed_precondition(
+                    "guesses are only allowed while the game is in progress",
                 ));
             }
+            if game_state.turn.as_deref() != Some(guess.player_id.as_str()) {
+                return Err(Status::failed_precondition("it is not this player's turn"));
+            }
+
+            let board_size = game_state.rules.board_size;
+            if guess.x as usize >= board_size || guess.y as usize >= board_size {
+                return Err(Status::invalid_argument("guess is off the board"));
+            }
+            let cells = weapon_cells(guess.weapon, guess.x, guess.y, guess.orientation, board_size)?;
+
+            // Refuse a shot aimed at a cell this player has already fired at,
+            // so a player can't park on a hit to hold the turn or farm charges.
+            let shooter = game_state
+                .player_mut(&guess.player_id)
+                .ok_or_else(|| Status::not_found("unknown player"))?;
+            if shooter.guesses.contains(&(guess.x, guess.y)) {
+                return Err(Status::failed_precondition(format!(
+                    "cell ({}, {}) has already been fired at",
+                    guess.x, guess.y
+                )));
+            }
+
+            // Powerful weapons cost a charge earned from normal hits.
+            if guess.weapon != 0 {
+                let shooter = game_state
+                    .player_mut(&guess.player_id)
+                    .ok_or_else(|| Status::not_found("unknown player"))?;
+                if shooter.charges == 0 {
+                    return Err(Status::failed_precondition(
+                        "no weapon charge available for this weapon",
+                    ));
+                }
+                shooter.charges -= 1;
+            }
+
+            let (outcomes, _fleet_destroyed) =
+                resolve_shots(&mut game_state, &guess.player_id, &cells)?;
+            award_charges(&mut game_state, &guess.player_id, guess.weapon, &outcomes);
+
+            // If a computer opponent now holds the turn, play its moves before
+            // replying so control returns to the human caller.
+            drive_ai(&mut game_state);
+            // The game may have ended on either the caller's volley or the AI's
+            // reply, so key cleanup on the final status rather than the caller's
+            // own destroy flag.
+            (outcomes, game_state.status == GameStatus::Finished)
+        };
+
+        if finished {
+            self.cleanup(&guess.game_id, &game).await;
+        }
+
+        Ok(Response::new(GuessResult {
+            hit: outcomes.iter().any(|o| o.hit),
+            sunk: outcomes.iter().any(|o| o.sunk),
+            cells: outcomes
+                .iter()
+                .map(|o| CellResult {
+                    x: o.x,
+                    y: o.y,
+                    hit: o.hit,
+                    sunk: o.sunk,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_game_state(
+        &self,
+        request: Request<Player>,
+    ) -> Result<Response<GameStateView>, Status> {
+        let player = request.into_inner();
+        let game = self.game(&player.game_id).await?;
+        let game_state = game.inner.lock().await;
+        let turn = game_state
+            .turn
+            .as_ref()
+            .and_then(|id| game_state.players.iter().find(|p| &p.id == id))
+            .map(|p| p.name.clone())
+            .unwrap_or_default();
+        Ok(Response::new(GameStateView {
+            status: game_state.status.as_proto(),
+            turn,
+        }))
+    }
+
+    async fn subscribe_events(
+        &self,
+        request: Request<Player>,
+    ) -> Result<Response<Self::SubscribeEventsStream>, Status> {
+        let player = request.into_inner();
+        let game = self.game(&player.game_id).await?;
+
+        // Subscribe first, then read the bootstrap snapshot, so no event can
+        // slip through between the two steps.
+        let (mut rx, snapshot) = {
+            let game_state = game.inner.lock().await;
+            let rx = game_state.events_tx.subscribe();
+            let mut cells = Vec::new();
+            for p in &game_state.players {
+                for (y, row) in p.board.iter().enumerate() {
+                    for (x, state) in row.iter().enumerate() {
+                        let label = match state {
+                            CellState::Hit => "hit",
+                            CellState::Miss => "miss",
+                            _ => continue,
+                        };
+                        cells.push(BoardCell {
+                            owner_id: p.id.clone(),
+                            x: x as u32,
+                            y: y as u32,
+                            state: label.to_string(),
+                        });
+                    }
+                }
+            }
+            let snapshot = GameEvent {
+                payload: Some(Payload::BoardSnapshot(BoardSnapshot {
+                    board_size: game_state.rules.board_size as u32,
+                    turn: game_state.turn.clone().unwrap_or_default(),
+                    cells,
+                })),
+            };
+            (rx, snapshot)
+        };
+
+        let (tx, out_rx) = mpsc::channel(100);
+        tokio::spawn(async move {
+            // Bootstrap the newcomer with the current turn and the revealed
+            // board state so a mid-game subscriber can render the match.
+            if tx.send(Ok(snapshot)).await.is_err() {
+                return;
+            }
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        if tx.send(Ok(event.to_proto())).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(out_rx))))
+    }
+}
+
+/// Register a player into `game`, transitioning to `WaitingForPlacement` once
+/// two players are present. Shared by `join_game` and `register_player`.
+async fn register_into(game: &GameState, player: &Player) -> Result<PlayerInfo, Status> {
+    let mut game_state = game.inner.lock().await;
+    if game_state.status != GameStatus::Created {
+        return Err(Status::failed_precondition(
+            "game is no longer accepting registrations",
+        ));
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let board_size = game_state.rules.board_size;
+    game_state
+        .players
+        .push(PlayerState::new(id.clone(), player.name.clone(), board_size));
+    let _ = game_state.events_tx.send(Event::PlayerJoined(player.clone()));
+
+    if game_state.players.len() == 2 {
+        game_state.status = GameStatus::WaitingForPlacement;
+    }
+
+    Ok(PlayerInfo { id })
+}
+
+/// Outcome of firing at a single cell.
+struct CellOutcome {
+    x: u32,
+    y: u32,
+    hit: bool,
+    sunk: bool,
+}
+
+/// Expand a weapon fired at `(x, y)` into the cells it strikes, validating the
+/// weapon selector. `SingleShot` hits the target; `CrossBomb` adds its four
+/// orthogonal neighbours; `LineShot` sweeps the whole row or column.
+fn weapon_cells(
+    weapon: i32,
+    x: u32,
+    y: u32,
+    orientation: i32,
+    board_size: usize,
+) -> Result<Vec<(u32, u32)>, Status> {
+    let n = board_size as u32;
+    let cells = match weapon {
+        // SINGLE_SHOT
+        0 => vec![(x, y)],
+        // CROSS_BOMB: target plus in-bounds orthogonal neighbours.
+        1 => {
+            let mut cells = vec![(x, y)];
+            if x > 0 {
+                cells.push((x - 1, y));
+            }
+            if x + 1 < n {
+                cells.push((x + 1, y));
+            }
+            if y > 0 {
+                cells.push((x, y - 1));
+            }
+            if y + 1 < n {
+                cells.push((x, y + 1));
+            }
+            cells
+        }
+        // LINE_SHOT: the whole row (horizontal) or column (vertical).
+        2 => {
+            if orientation == 0 {
+                (0..n).map(|cx| (cx, y)).collect()
+            } else {
+                (0..n).map(|cy| (x, cy)).collect()
+            }
+        }
+        _ => return Err(Status::invalid_argument("unknown weapon")),
+    };
+    Ok(cells)
+}
 
-            let (length, cells) = match ship_type {
-                ShipType::Carrier => (CARRIER_LENGTH, &mut player_state.ships[0]),
-                ShipType::Battles => (BATTLESHIP_LENGTH, &mut player_state.ships[1]),
-                ShipType::Cruiser => (CRUISER_LENGTH, &mut player_state.ships[2]),
-                ShipType::Submarine => (SUBMARINE_LENGTH, &mut player_state.ships[3]),
-                ShipType::Destroyer => (DESTROYER_LENGTH, &mut player_state.ships[4]),
+/// Resolve `shooter`'s shots against the opponent, recording each cell and
+/// emitting events, then advance the turn/status from the aggregate result.
+/// Shared by the human `make_guess` handler and the AI driver.
+fn resolve_shots(
+    game_state: &mut GameStateInner,
+    shooter_id: &str,
+    cells: &[(u32, u32)],
+) -> Result<(Vec<CellOutcome>, bool), Status> {
+    let opponent = game_state
+        .opponent_index(shooter_id)
+        .ok_or_else(|| Status::not_found("opponent not found"))?;
+    let board_size = game_state.rules.board_size;
+    let owner_id = game_state.players[opponent].id.clone();
+
+    let mut outcomes = Vec::with_capacity(cells.len());
+    let mut any_hit = false;
+    for &(gx, gy) in cells {
+        let (x, y) = (gx as usize, gy as usize);
+        if x >= board_size || y >= board_size {
+            return Err(Status::invalid_argument("guess is off the board"));
+        }
+        // A weapon pattern may sweep over cells already resolved on an earlier
+        // turn; those are inert and must not re-count toward hits or charges.
+        if matches!(
+            game_state.players[opponent].board[y][x],
+            CellState::Hit | CellState::Miss
+        ) {
+            continue;
+        }
+
+        let (hit, sunk, struck_ship) = {
+            let target = &mut game_state.players[opponent];
+            let struck_ship = target.ship_positions[y][x];
+            let hit = match struck_ship {
+                Some(_) => {
+                    target.board[y][x] = CellState::Hit;
+                    true
+                }
+                None => {
+                    target.board[y][x] = CellState::Miss;
+                    false
+                }
             };
+            let sunk = hit && ship_sunk_at(target, x, y);
+            (hit, sunk, struck_ship)
+        };
+        any_hit |= hit;
+
+        let _ = game_state.events_tx.send(Event::ShotFired {
+            player_id: shooter_id.to_string(),
+            x: gx,
+            y: gy,
+            hit,
+        });
+        if let (true, Some(ship)) = (sunk, struck_ship) {
+            let _ = game_state.events_tx.send(Event::ShipSunk {
+                owner_id: owner_id.clone(),
+                ship_type: ship.wire_name().to_string(),
+            });
+        }
+        outcomes.push(CellOutcome {
+            x: gx,
+            y: gy,
+            hit,
+            sunk,
+        });
+    }
+
+    let fleet_destroyed = game_state.players[opponent]
+        .board
+        .iter()
+        .flatten()
+        .all(|c| *c != CellState::Occupied);
+
+    if let Some(player) = game_state.player_mut(shooter_id) {
+        // Only cells actually resolved this volley are recorded; inert repeats
+        // were skipped above and never reach `outcomes`.
+        for o in &outcomes {
+            player.guesses.push((o.x, o.y));
+        }
+    }
+
+    if fleet_destroyed {
+        game_state.status = GameStatus::Finished;
+        game_state.turn = None;
+        let _ = game_state.events_tx.send(Event::GameOver {
+            winner_id: shooter_id.to_string(),
+        });
+    } else if !any_hit {
+        // A volley that lands no hits passes the turn to the opponent.
+        let next = game_state.players[opponent].id.clone();
+        game_state.turn = Some(next.clone());
+        let _ = game_state.events_tx.send(Event::PlayerTurn(next));
+    }
+
+    Ok((outcomes, fleet_destroyed))
+}
+
+/// Credit a player's normal hits toward special-weapon charges. Only
+/// `SingleShot` volleys count, so a special weapon can never refund the charge
+/// it just spent.
+fn award_charges(
+    game_state: &mut GameStateInner,
+    shooter_id: &str,
+    weapon: i32,
+    outcomes: &[CellOutcome],
+) {
+    if weapon != 0 {
+        return;
+    }
+    let hits = outcomes.iter().filter(|o| o.hit).count() as u32;
+    if let Some(player) = game_state.player_mut(shooter_id) {
+        player.hits_toward_charge += hits;
+        while player.hits_toward_charge >= HITS_PER_CHARGE {
+            player.hits_toward_charge -= HITS_PER_CHARGE;
+            player.charges += 1;
+        }
+    }
+}
+
+/// Play the computer opponent's shots for as long as it holds the turn.
+fn drive_ai(game_state: &mut GameStateInner) {
+    let ai_id = match &game_state.ai {
+        Some(ai) => ai.id.clone(),
+        None => return,
+    };
+    while game_state.status == GameStatus::InProgress
+        && game_state.turn.as_deref() == Some(ai_id.as_str())
+    {
+        let Some((x, y)) = game_state.ai.as_ref().and_then(|ai| ai.engine.next_guess()) else {
+            // The engine has no cell to fire at (e.g. its ship inventory
+            // desynced from the board and zeroed the density grid). Hand the
+            // turn back to the opponent instead of stalling with it pinned here.
+            if let Some(opponent) = game_state
+                .players
+                .iter()
+                .find(|p| p.id != ai_id)
+                .map(|p| p.id.clone())
+            {
+                game_state.turn = Some(opponent.clone());
+                let _ = game_state.events_tx.send(Event::PlayerTurn(opponent));
+            }
+            break;
+        };
+        let Ok((outcomes, _)) = resolve_shots(game_state, &ai_id, &[(x, y)]) else {
+            break;
+        };
+        let outcome = &outcomes[0];
+        if let Some(ai) = game_state.ai.as_mut() {
+            ai.engine.record(x, y, outcome.hit, outcome.sunk);
+        }
+    }
+}
+
+/// Flatten a rules fleet into the list of ship lengths, one entry per ship.
+fn fleet_lengths(rules: &GameRules) -> Vec<usize> {
+    rules
+        .fleet
+        .iter()
+        .flat_map(|(ship, count)| std::iter::repeat(ship.length()).take(*count as usize))
+        .collect()
+}
+
+/// Deterministically place every required ship for `player`, scanning for the
+/// first non-overlapping, in-bounds position. Used to seat the AI's fleet.
+fn auto_place_fleet(player: &mut PlayerState, rules: &GameRules) -> Result<(), ()> {
+    let n = rules.board_size;
+    for (ship, count) in &rules.fleet {
+        let len = ship.length();
+        for _ in 0..*count {
+            let mut placed = false;
+            'scan: for y in 0..n {
+                for x in 0..n {
+                    for (dx, dy) in [(1usize, 0usize), (0, 1)] {
+                        if x + dx * (len - 1) >= n || y + dy * (len - 1) >= n {
+                            continue;
+                        }
+                        let cells: Vec<Cell> = (0..len)
+                            .map(|i| Cell {
+                                x: (x + dx * i) as u32,
+                                y: (y + dy * i) as u32,
+                            })
+                            .collect();
+                        if cells
+                            .iter()
+                            .any(|c| player.ship_positions[c.y as usize][c.x as usize].is_some())
+                        {
+                            continue;
+                        }
+                        for c in &cells {
+                            player.ship_positions[c.y as usize][c.x as usize] = Some(*ship);
+                            player.board[c.y as usize][c.x as usize] = CellState::Occupied;
+                        }
+                        player.ship_cells.entry(*ship).or_default().push(cells);
+                        placed = true;
+                        break 'scan;
+                    }
+                }
+            }
+            if !placed {
+                return Err(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether the ship occupying `(x, y)` on `target` has had every cell hit.
+fn ship_sunk_at(target: &PlayerState, x: usize, y: usize) -> bool {
+    let Some(ship) = target.ship_positions[y][x] else {
+        return false;
+    };
+    let Some(ships) = target.ship_cells.get(&ship) else {
+        return false;
+    };
+    ships
+        .iter()
+        .find(|cells| cells.iter().any(|c| c.x as usize == x && c.y as usize == y))
+        .map(|cells| {
+            cells
+                .iter()
+                .all(|c| target.board[c.y as usize][c.x as usize] == CellState::Hit)
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placement(ship: &str, x: u32, y: u32, orientation: i32) -> ShipPlacement {
+        ShipPlacement {
+            game_id: String::new(),
+            player_id: "p".to_string(),
+            ship_type: ship.to_string(),
+            x,
+            y,
+            orientation,
         }
-        Ok(Response::new({}));
     }
 
-    fn make_guess(&self, request: Request<Guess>) -> Result<Response<Guess>, Status> {
-        !todo!();
+    fn rules() -> GameRules {
+        GameRules {
+            board_size: 10,
+            fleet: vec![(ShipType::Destroyer, 2), (ShipType::Cruiser, 1)],
+            allow_diagonal: false,
+        }
+    }
+
+    fn player() -> PlayerState {
+        PlayerState::new("p".to_string(), "P".to_string(), 10)
+    }
+
+    #[test]
+    fn place_one_rejects_unknown_ship() {
+        let err = place_one(&mut player(), &rules(), &placement("galleon", 0, 0, 0)).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn place_one_rejects_ship_off_board() {
+        // Cruiser is length 3; starting at x=8 horizontally runs off a 10-wide board.
+        let err = place_one(&mut player(), &rules(), &placement("cruiser", 8, 0, 0)).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn place_one_rejects_overlap() {
+        let mut p = player();
+        place_one(&mut p, &rules(), &placement("destroyer", 0, 0, 0)).unwrap();
+        // A second destroyer crossing the first at (0, 0) must be refused.
+        let err = place_one(&mut p, &rules(), &placement("destroyer", 0, 0, 1)).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::AlreadyExists);
+    }
+
+    #[test]
+    fn place_one_rejects_more_than_required() {
+        let mut p = player();
+        place_one(&mut p, &rules(), &placement("destroyer", 0, 0, 0)).unwrap();
+        place_one(&mut p, &rules(), &placement("destroyer", 0, 2, 0)).unwrap();
+        // Only two destroyers are part of the fleet.
+        let err = place_one(&mut p, &rules(), &placement("destroyer", 0, 4, 0)).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::AlreadyExists);
+    }
+
+    #[test]
+    fn place_one_rejects_diagonal_unless_allowed() {
+        let mut rules = rules();
+        let err = place_one(&mut player(), &rules, &placement("cruiser", 0, 0, 2)).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        rules.allow_diagonal = true;
+        place_one(&mut player(), &rules, &placement("cruiser", 0, 0, 2)).unwrap();
     }
 
-    fn get_game_state(&self, request: Request<Player>) -> Result<Response<GameState>, Status> {
-        !todo!();
+    #[test]
+    fn fleet_ready_tracks_required_counts() {
+        let rules = rules();
+        let mut p = player();
+        assert!(!p.fleet_ready(&rules));
+        place_one(&mut p, &rules, &placement("destroyer", 0, 0, 0)).unwrap();
+        place_one(&mut p, &rules, &placement("destroyer", 0, 2, 0)).unwrap();
+        place_one(&mut p, &rules, &placement("cruiser", 0, 4, 0)).unwrap();
+        assert!(p.fleet_ready(&rules));
     }
 }